@@ -0,0 +1,10 @@
+use crate::*;
+
+impl AvrRt {
+    /// Returns the asynchronous handle for the given UART (`'0'`, `'1'`, …).
+    ///
+    /// See: [`UartAsync`].
+    pub fn uart(&self, id: char) -> UartAsync {
+        UartAsync::new(id)
+    }
+}
@@ -0,0 +1,65 @@
+use super::*;
+use simavr_ffi as ffi;
+
+/// Thin, owned handle to simavr's `avr_t`.
+pub struct Avr {
+    ptr: *mut ffi::avr_t,
+}
+
+impl Avr {
+    pub(crate) fn new(ptr: *mut ffi::avr_t) -> Self {
+        Self { ptr }
+    }
+
+    pub(crate) fn ptr(&self) -> *mut ffi::avr_t {
+        self.ptr
+    }
+
+    /// Issues an `ioctl` request against the underlying `avr_t`.
+    ///
+    /// # Safety
+    ///
+    /// `param` must point to a value of the type the given [`IoCtl`] expects.
+    pub(crate) unsafe fn ioctl<T>(&mut self, ioctl: IoCtl, param: &mut T) -> i32 {
+        ffi::avr_ioctl(self.ptr, ioctl.into_ffi(), param as *mut T as *mut _)
+    }
+
+    /// Looks up an IRQ registered by a peripheral.
+    ///
+    /// # Safety
+    ///
+    /// The returned pointer is only valid for as long as the underlying
+    /// `avr_t` is alive.
+    pub(crate) unsafe fn io_getirq(&mut self, ioctl: IoCtl, irq: u32) -> *mut ffi::avr_irq_t {
+        ffi::avr_io_getirq(self.ptr, ioctl.into_ffi(), irq as _)
+    }
+
+    /// Returns the AVR's clock frequency, in Hz.
+    pub(crate) fn frequency(&self) -> u32 {
+        unsafe { (*self.ptr).frequency }
+    }
+
+    /// Returns the number of CPU cycles the AVR has executed so far.
+    pub(crate) fn cycle(&self) -> u64 {
+        unsafe { (*self.ptr).cycle }
+    }
+
+    /// Reads a byte directly out of the AVR's address space (`avr_t::data`).
+    ///
+    /// simavr doesn't expose an `ioctl` for arbitrary peripheral registers -
+    /// config/status registers such as `UCSRnA`/`UCSRnB`/`UCSRnC`/`UBRRn` are
+    /// plain memory as far as the core is concerned, so this is the same
+    /// mechanism simavr's own gdb stub uses to inspect them.
+    pub(crate) fn peek(&self, addr: u16) -> u8 {
+        unsafe { *(*self.ptr).data.add(addr as usize) }
+    }
+
+    /// Writes a byte directly into the AVR's address space (`avr_t::data`).
+    ///
+    /// See: [`Self::peek()`].
+    pub(crate) fn poke(&mut self, addr: u16, value: u8) {
+        unsafe {
+            *(*self.ptr).data.add(addr as usize) = value;
+        }
+    }
+}
@@ -0,0 +1,27 @@
+/// Typed wrapper around simavr's `ioctl`-style peripheral requests.
+///
+/// Each variant carries the fields the underlying `avr_ioctl()` /
+/// `avr_io_getirq()` call needs to address a specific UART instance.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IoCtl {
+    UartGetFlags { uart: u8 },
+    UartSetFlags { uart: u8 },
+    UartGetIrq { uart: u8 },
+}
+
+impl IoCtl {
+    /// Encodes this request the way simavr's `AVR_IOCTL_DEF()` macro does:
+    /// four tag bytes packed into a single `u32`, with the UART's id as the
+    /// last byte.
+    pub(crate) fn into_ffi(self) -> u32 {
+        match self {
+            Self::UartGetFlags { uart } => Self::tag(*b"uaf", uart),
+            Self::UartSetFlags { uart } => Self::tag(*b"uas", uart),
+            Self::UartGetIrq { uart } => Self::tag(*b"uai", uart),
+        }
+    }
+
+    fn tag(prefix: [u8; 3], uart: u8) -> u32 {
+        u32::from_be_bytes([prefix[0], prefix[1], prefix[2], uart])
+    }
+}
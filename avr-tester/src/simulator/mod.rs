@@ -0,0 +1,80 @@
+mod avr;
+mod ioctl;
+pub(crate) mod uart;
+
+pub(crate) use avr::Avr;
+pub(crate) use ioctl::IoCtl;
+
+use crate::uart::{LineError, UartConfig};
+use uart::Uart;
+
+/// Owns the running simavr instance and forwards peripheral-level requests
+/// (e.g. from [`crate::uart::Uart`]) to the individual component wrappers
+/// (e.g. [`uart::Uart`]).
+pub struct AvrSimulator {
+    avr: Avr,
+    uarts: Vec<Uart>,
+}
+
+impl AvrSimulator {
+    pub(crate) fn new(avr: Avr, uarts: Vec<Uart>) -> Self {
+        Self { avr, uarts }
+    }
+
+    fn uart(&mut self, id: char) -> &mut Uart {
+        self.uarts
+            .iter_mut()
+            .find(|uart| uart.id() == id as u8)
+            .unwrap_or_else(|| panic!("unknown or unsupported UART: {}", id))
+    }
+
+    fn uart_and_avr(&mut self, id: char) -> (&mut Uart, &mut Avr) {
+        let uart = self
+            .uarts
+            .iter_mut()
+            .find(|uart| uart.id() == id as u8)
+            .unwrap_or_else(|| panic!("unknown or unsupported UART: {}", id));
+
+        (uart, &mut self.avr)
+    }
+
+    pub(crate) fn read_uart(&mut self, id: char) -> Option<u8> {
+        self.uart(id).recv()
+    }
+
+    pub(crate) fn write_uart(&mut self, id: char, byte: u8) {
+        self.uart(id).send(byte);
+    }
+
+    pub(crate) fn configure_uart(&mut self, id: char, config: UartConfig) {
+        self.uart(id).configure(config);
+    }
+
+    pub(crate) fn uart_frame_format(&mut self, id: char) -> UartConfig {
+        let (uart, avr) = self.uart_and_avr(id);
+
+        uart.frame_format(avr)
+    }
+
+    pub(crate) fn read_uart_word(&mut self, id: char) -> Option<u16> {
+        self.uart(id).recv_word()
+    }
+
+    pub(crate) fn write_uart_word(&mut self, id: char, word: u16) {
+        self.uart(id).send_word(word);
+    }
+
+    pub(crate) fn inject_uart_error(&mut self, id: char, error: LineError) {
+        self.uart(id).inject_error(error);
+    }
+
+    pub(crate) fn uart_baud_rate(&mut self, id: char) -> u32 {
+        let (uart, avr) = self.uart_and_avr(id);
+
+        uart.baud_rate(avr)
+    }
+
+    pub(crate) fn uart_take_error(&mut self, id: char) -> bool {
+        self.uart(id).take_rx_error()
+    }
+}
@@ -1,7 +1,19 @@
 use super::*;
+use crate::uart::{DataBits, LineError, Parity, StopBits, UartConfig};
 use simavr_ffi as ffi;
 use std::{cell::UnsafeCell, collections::VecDeque, ffi::c_void};
 
+/// `FEn` bit within `UCSRnA` (set while a break condition is being held).
+const UCSRNA_FE: u32 = 1 << 4;
+
+/// `U2Xn` bit within `UCSRnA` (doubles the UART's transfer rate).
+const UCSRNA_U2X: u8 = 1 << 1;
+
+/// `RXB8n` bit within `UCSRnB` (the 9th data bit of the byte *being received*
+/// by the AVR - not to be confused with `TXB8n`, which carries the 9th bit
+/// of whatever the AVR itself last transmitted).
+const UCSRNB_RXB8: u8 = 1 << 1;
+
 pub struct Uart {
     ptr: *mut UartT,
     id: u8,
@@ -15,6 +27,34 @@ impl Uart {
         }
     }
 
+    pub fn id(&self) -> u8 {
+        self.id
+    }
+
+    /// Address of `UCSRnA` for this UART, following the fixed 8-byte-per-UART
+    /// register block every AVR part this crate targets lays `UCSRnA` /
+    /// `UCSRnB` / `UCSRnC` / `UBRRnL` / `UBRRnH` out in, starting at `0xC0`
+    /// for UART0 (see e.g. `<avr/iom328p.h>`).
+    fn ucsrna_addr(&self) -> u16 {
+        0xC0 + (self.id - b'0') as u16 * 8
+    }
+
+    fn ucsrnb_addr(&self) -> u16 {
+        self.ucsrna_addr() + 1
+    }
+
+    fn ucsrnc_addr(&self) -> u16 {
+        self.ucsrna_addr() + 2
+    }
+
+    fn ubrrl_addr(&self) -> u16 {
+        self.ucsrna_addr() + 4
+    }
+
+    fn ubrrh_addr(&self) -> u16 {
+        self.ucsrna_addr() + 5
+    }
+
     pub fn try_init(self, avr: &mut Avr) -> Option<Self> {
         let mut flags: u32 = 0;
 
@@ -70,6 +110,46 @@ impl Uart {
 
     pub fn flush(&mut self, avr: &mut Avr) {
         let this = unsafe { &*self.ptr };
+
+        // `FEn` can be raised by an injected framing error and by a break
+        // condition at the same time (a break *is* a framing error, just
+        // held for longer) - both go through this single release cycle so
+        // that whichever one asked for the longer hold wins, instead of one
+        // clearing the bit out from under the other.
+        if let Some(until) = this.fe_release_at() {
+            if avr.cycle() >= until {
+                this.clear_fe_release_at();
+                self.clear_ucsrna_bits(avr, UCSRNA_FE);
+            }
+        }
+
+        if let Some((mask, until)) = this.held_error() {
+            if avr.cycle() >= until {
+                this.clear_held_error();
+                self.clear_ucsrna_bits(avr, mask);
+            }
+        }
+
+        if let Some(until) = this.break_until() {
+            if avr.cycle() < until {
+                // Still holding the RX line low - nothing more to do until the
+                // break condition elapses.
+                return;
+            }
+
+            this.clear_break_until();
+        } else if let Some(bit_times) = this.take_pending_break() {
+            let cycles_per_bit = self.cycles_per_bit(avr).max(1);
+            let until = avr.cycle() + cycles_per_bit * bit_times as u64;
+
+            self.set_ucsrna_bits(avr, UCSRNA_FE);
+            self.raise_input(avr, 0);
+            this.set_break_until(until);
+            this.extend_fe_release(until);
+            return;
+        }
+
+        let cycles_per_frame = self.cycles_per_frame(avr);
         let mut irq = None;
 
         loop {
@@ -77,12 +157,22 @@ impl Uart {
                 break;
             }
 
+            if !this.ready_to_send(avr.cycle(), cycles_per_frame) {
+                break;
+            }
+
             let byte = if let Some(byte) = this.tx_pop() {
                 byte
             } else {
                 break;
             };
 
+            let pending_error_mask = this.take_pending_error().and_then(|error| error.ucsrna_mask());
+
+            if let Some(mask) = pending_error_mask {
+                self.set_ucsrna_bits(avr, mask);
+            }
+
             let irq = irq.get_or_insert_with(|| {
                 let ioctl = IoCtl::UartGetIrq { uart: self.id }.into_ffi();
                 let irq = unsafe { ffi::avr_io_getirq(avr.ptr(), ioctl, ffi::UART_IRQ_INPUT as _) };
@@ -94,26 +184,200 @@ impl Uart {
                 irq
             });
 
+            // simavr's UART input FIFO is `uint8_t`, so the 9th data bit
+            // can't ride along on the `UART_IRQ_INPUT` value itself; for
+            // 9-bit frames we poke it into `UCSRnB`'s `RXB8n` bit first,
+            // mirroring what real hardware latches before `UDRn` is updated.
+            if this.config().data_bits == DataBits::Nine {
+                let addr = self.ucsrnb_addr();
+                let mut ucsrnb = avr.peek(addr);
+
+                if (byte >> 8) & 1 != 0 {
+                    ucsrnb |= UCSRNB_RXB8;
+                } else {
+                    ucsrnb &= !UCSRNB_RXB8;
+                }
+
+                avr.poke(addr, ucsrnb);
+            }
+
             unsafe {
                 ffi::avr_raise_irq(*irq, byte as _);
             }
+
+            this.set_last_tx_cycle(avr.cycle());
+
+            // simavr doesn't model FE/UPE/DOR auto-clearing the way real
+            // hardware does on every frame, so we have to do it ourselves -
+            // but not before firmware gets a chance to see it. flush() runs
+            // synchronously with no AVR instructions stepped in between, so
+            // clearing the bit here (rather than holding it until a later
+            // flush() call, once a full frame-time has elapsed) would mean
+            // firmware's ISR never observes it.
+            if let Some(mask) = pending_error_mask {
+                let until = avr.cycle() + cycles_per_frame.max(1);
+
+                if mask == UCSRNA_FE {
+                    // A framing error raises the same bit a break does -
+                    // route it through the shared release cycle so it
+                    // can't be clobbered by (or clobber) an overlapping
+                    // break.
+                    this.extend_fe_release(until);
+                } else {
+                    this.set_held_error(mask, until);
+                }
+            }
         }
     }
 
+    /// Returns the baud rate the firmware has programmed into this UART,
+    /// decoded from `UBRRn` and `U2Xn` the way the HAL drivers compute it:
+    /// `f_cpu / ((U2Xn ? 8 : 16) * (UBRRn + 1))`.
+    pub fn baud_rate(&self, avr: &mut Avr) -> u32 {
+        let ubrrl = avr.peek(self.ubrrl_addr()) as u32;
+        let ubrrh = avr.peek(self.ubrrh_addr()) as u32;
+        let ubrr = ubrrl | ((ubrrh & 0x0f) << 8);
+
+        let u2x = avr.peek(self.ucsrna_addr()) & UCSRNA_U2X != 0;
+
+        Self::decode_baud_rate(avr.frequency(), ubrr, u2x)
+    }
+
+    /// Decodes a baud rate from `UBRRn` and `U2Xn` the way the HAL drivers
+    /// compute it: `f_cpu / ((U2Xn ? 8 : 16) * (UBRRn + 1))`.
+    fn decode_baud_rate(f_cpu: u32, ubrr: u32, u2x: bool) -> u32 {
+        let divisor = if u2x { 8 } else { 16 };
+
+        f_cpu / (divisor * (ubrr + 1))
+    }
+
+    /// Returns the number of CPU cycles a single frame (start bit + data
+    /// bits + parity bit + stop bits) takes to transmit at the firmware's
+    /// programmed baud rate.
+    fn cycles_per_frame(&self, avr: &mut Avr) -> u64 {
+        let this = unsafe { &*self.ptr };
+        let baud_rate = self.baud_rate(avr) as u64;
+
+        if baud_rate == 0 {
+            return 0;
+        }
+
+        let config = this.config();
+        let data_bits = match config.data_bits {
+            DataBits::Eight => 8,
+            DataBits::Nine => 9,
+        };
+        let parity_bits = if config.parity == Parity::None { 0 } else { 1 };
+        let stop_bits = match config.stop_bits {
+            StopBits::One => 1,
+            StopBits::Two => 2,
+        };
+        let bits_per_frame = 1 + data_bits + parity_bits + stop_bits;
+
+        (avr.frequency() as u64 * bits_per_frame) / baud_rate
+    }
+
+    /// Returns the number of CPU cycles a single bit-time takes at the
+    /// firmware's programmed baud rate.
+    fn cycles_per_bit(&self, avr: &mut Avr) -> u64 {
+        let baud_rate = self.baud_rate(avr) as u64;
+
+        if baud_rate == 0 {
+            return 0;
+        }
+
+        avr.frequency() as u64 / baud_rate
+    }
+
+    /// Arranges for the next byte delivered to the AVR (or, for
+    /// [`LineError::Break`], the next several bit-times) to carry the given
+    /// line error.
+    pub fn inject_error(&mut self, error: LineError) {
+        let this = unsafe { &*self.ptr };
+
+        if let LineError::Break { bit_times } = error {
+            this.set_pending_break(Some(bit_times));
+        } else {
+            this.set_pending_error(Some(error));
+        }
+    }
+
+    fn set_ucsrna_bits(&self, avr: &mut Avr, mask: u32) {
+        let addr = self.ucsrna_addr();
+
+        avr.poke(addr, avr.peek(addr) | mask as u8);
+    }
+
+    fn clear_ucsrna_bits(&self, avr: &mut Avr, mask: u32) {
+        let addr = self.ucsrna_addr();
+
+        avr.poke(addr, avr.peek(addr) & !(mask as u8));
+    }
+
+    fn raise_input(&self, avr: &mut Avr, value: u32) {
+        let ioctl = IoCtl::UartGetIrq { uart: self.id }.into_ffi();
+
+        unsafe {
+            let irq = ffi::avr_io_getirq(avr.ptr(), ioctl, ffi::UART_IRQ_INPUT as _);
+
+            if irq.is_null() {
+                panic!("avr_io_getirq() failed (got a null pointer)")
+            }
+
+            ffi::avr_raise_irq(irq, value);
+        }
+    }
+
+    /// Returns (and clears) whether a byte sent by the AVR was dropped
+    /// because the test harness hadn't read the previous one yet - i.e. an
+    /// overrun on the path this reads from.
+    pub fn take_rx_error(&mut self) -> bool {
+        let this = unsafe { &*self.ptr };
+
+        this.take_rx_had_error()
+    }
+
     pub fn recv(&mut self) -> Option<u8> {
         let this = unsafe { &*self.ptr };
 
+        this.rx_pop().map(|word| word as u8)
+    }
+
+    pub fn recv_word(&mut self) -> Option<u16> {
+        let this = unsafe { &*self.ptr };
+
         this.rx_pop()
     }
 
     pub fn send(&mut self, byte: u8) {
         let this = unsafe { &*self.ptr };
 
-        this.tx_push(byte);
+        this.tx_push(byte as u16);
+    }
+
+    pub fn send_word(&mut self, word: u16) {
+        let this = unsafe { &*self.ptr };
+
+        this.tx_push(word);
+    }
+
+    pub fn configure(&mut self, config: UartConfig) {
+        let this = unsafe { &*self.ptr };
+
+        this.set_config(config);
+    }
+
+    /// Returns the frame format the firmware has actually programmed into
+    /// this UART, decoded from `UCSRnB` / `UCSRnC`.
+    pub fn frame_format(&self, avr: &mut Avr) -> UartConfig {
+        let ucsrnb = avr.peek(self.ucsrnb_addr());
+        let ucsrnc = avr.peek(self.ucsrnc_addr());
+
+        UartConfig::from_registers(ucsrnb, ucsrnc)
     }
 
     unsafe extern "C" fn on_output(_: *mut ffi::avr_irq_t, value: u32, uart: *mut c_void) {
-        UartT::from_ptr(uart).rx_push(value as u8);
+        UartT::from_ptr(uart).rx_push(value as u16);
     }
 
     unsafe extern "C" fn on_xon(_: *mut ffi::avr_irq_t, _: u32, uart: *mut c_void) {
@@ -135,9 +399,17 @@ impl Drop for Uart {
 
 #[derive(Debug)]
 pub struct UartT {
-    rx: UnsafeCell<VecDeque<u8>>,
-    tx: UnsafeCell<VecDeque<u8>>,
+    rx: UnsafeCell<VecDeque<u16>>,
+    tx: UnsafeCell<VecDeque<u16>>,
     xon: UnsafeCell<bool>,
+    config: UnsafeCell<UartConfig>,
+    pending_error: UnsafeCell<Option<LineError>>,
+    pending_break: UnsafeCell<Option<u32>>,
+    break_until: UnsafeCell<Option<u64>>,
+    last_tx_cycle: UnsafeCell<Option<u64>>,
+    rx_had_error: UnsafeCell<bool>,
+    held_error: UnsafeCell<Option<(u32, u64)>>,
+    fe_release_at: UnsafeCell<Option<u64>>,
 }
 
 impl UartT {
@@ -147,27 +419,31 @@ impl UartT {
         &*(uart as *mut Self)
     }
 
-    pub fn rx_push(&self, value: u8) {
+    pub fn rx_push(&self, value: u16) {
         let rx = unsafe { &mut *self.rx.get() };
 
         if rx.len() < Self::MAX_BYTES {
             rx.push_back(value);
+        } else {
+            // The test harness isn't reading fast enough and the AVR kept
+            // sending - that's a receive overrun on this path.
+            self.set_rx_had_error(true);
         }
     }
 
-    pub fn rx_pop(&self) -> Option<u8> {
+    pub fn rx_pop(&self) -> Option<u16> {
         let rx = unsafe { &mut *self.rx.get() };
 
         rx.pop_front()
     }
 
-    pub fn tx_push(&self, value: u8) {
+    pub fn tx_push(&self, value: u16) {
         let tx = unsafe { &mut *self.tx.get() };
 
         tx.push_back(value);
     }
 
-    pub fn tx_pop(&self) -> Option<u8> {
+    pub fn tx_pop(&self) -> Option<u16> {
         let tx = unsafe { &mut *self.tx.get() };
 
         tx.pop_front()
@@ -190,6 +466,139 @@ impl UartT {
 
         *xon = false;
     }
+
+    pub fn config(&self) -> UartConfig {
+        let config = unsafe { &*self.config.get() };
+
+        *config
+    }
+
+    pub fn set_config(&self, config: UartConfig) {
+        let dst = unsafe { &mut *self.config.get() };
+
+        *dst = config;
+    }
+
+    pub fn set_pending_error(&self, error: Option<LineError>) {
+        let dst = unsafe { &mut *self.pending_error.get() };
+
+        *dst = error;
+    }
+
+    pub fn take_pending_error(&self) -> Option<LineError> {
+        let dst = unsafe { &mut *self.pending_error.get() };
+
+        dst.take()
+    }
+
+    pub fn take_pending_break(&self) -> Option<u32> {
+        let dst = unsafe { &mut *self.pending_break.get() };
+
+        dst.take()
+    }
+
+    pub fn set_pending_break(&self, bit_times: Option<u32>) {
+        let dst = unsafe { &mut *self.pending_break.get() };
+
+        *dst = bit_times.filter(|&n| n > 0);
+    }
+
+    /// Returns the cycle at which a currently-held break condition ends, or
+    /// `None` if no break is in progress.
+    pub fn break_until(&self) -> Option<u64> {
+        let dst = unsafe { &*self.break_until.get() };
+
+        *dst
+    }
+
+    pub fn set_break_until(&self, cycle: u64) {
+        let dst = unsafe { &mut *self.break_until.get() };
+
+        *dst = Some(cycle);
+    }
+
+    pub fn clear_break_until(&self) {
+        let dst = unsafe { &mut *self.break_until.get() };
+
+        *dst = None;
+    }
+
+    /// Returns whether enough cycles have elapsed since the last byte was
+    /// handed to the AVR for the next one to be sent at `cycles_per_frame`.
+    pub fn ready_to_send(&self, now: u64, cycles_per_frame: u64) -> bool {
+        let last_tx_cycle = unsafe { &*self.last_tx_cycle.get() };
+
+        match *last_tx_cycle {
+            Some(last) => now.saturating_sub(last) >= cycles_per_frame,
+            None => true,
+        }
+    }
+
+    pub fn set_last_tx_cycle(&self, cycle: u64) {
+        let dst = unsafe { &mut *self.last_tx_cycle.get() };
+
+        *dst = Some(cycle);
+    }
+
+    pub fn set_rx_had_error(&self, had_error: bool) {
+        let dst = unsafe { &mut *self.rx_had_error.get() };
+
+        *dst = had_error;
+    }
+
+    pub fn take_rx_had_error(&self) -> bool {
+        let dst = unsafe { &mut *self.rx_had_error.get() };
+
+        std::mem::take(dst)
+    }
+
+    /// Returns the `(mask, until)` of an `UCSRnA` error condition currently
+    /// being held, if any - see [`Uart::flush()`].
+    pub fn held_error(&self) -> Option<(u32, u64)> {
+        let dst = unsafe { &*self.held_error.get() };
+
+        *dst
+    }
+
+    pub fn set_held_error(&self, mask: u32, until: u64) {
+        let dst = unsafe { &mut *self.held_error.get() };
+
+        *dst = Some((mask, until));
+    }
+
+    pub fn clear_held_error(&self) {
+        let dst = unsafe { &mut *self.held_error.get() };
+
+        *dst = None;
+    }
+
+    /// Returns the cycle at which `UCSRnA`'s `FEn` bit should be cleared, if
+    /// either an injected framing error or a break is currently holding it.
+    ///
+    /// A break and an injected framing error both raise the same bit, so
+    /// they share this single release cycle instead of each tracking their
+    /// own deadline - see [`Self::extend_fe_release()`].
+    pub fn fe_release_at(&self) -> Option<u64> {
+        let dst = unsafe { &*self.fe_release_at.get() };
+
+        *dst
+    }
+
+    /// Holds `FEn` until at least `until`, without shortening a hold that's
+    /// already pending for longer - e.g. a break started while an injected
+    /// framing error's (shorter) hold is still active must not have its
+    /// deadline pulled in.
+    pub fn extend_fe_release(&self, until: u64) {
+        let dst = unsafe { &mut *self.fe_release_at.get() };
+
+        *dst = Some(dst.map_or(until, |existing| existing.max(until)));
+    }
+
+    pub fn clear_fe_release_at(&self) {
+        let dst = unsafe { &mut *self.fe_release_at.get() };
+
+        *dst = None;
+    }
 }
 
 impl Default for UartT {
@@ -198,6 +607,142 @@ impl Default for UartT {
             rx: Default::default(),
             tx: Default::default(),
             xon: UnsafeCell::new(true),
+            config: UnsafeCell::new(UartConfig::default()),
+            pending_error: UnsafeCell::new(None),
+            pending_break: UnsafeCell::new(None),
+            break_until: UnsafeCell::new(None),
+            last_tx_cycle: UnsafeCell::new(None),
+            rx_had_error: UnsafeCell::new(false),
+            held_error: UnsafeCell::new(None),
+            fe_release_at: UnsafeCell::new(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pending_error_is_consumed_once() {
+        let uart = UartT::default();
+
+        uart.set_pending_error(Some(LineError::Framing));
+
+        assert_eq!(Some(LineError::Framing), uart.take_pending_error());
+        assert_eq!(None, uart.take_pending_error());
+    }
+
+    #[test]
+    fn pending_break_is_consumed_once() {
+        let uart = UartT::default();
+
+        uart.set_pending_break(Some(10));
+
+        assert_eq!(Some(10), uart.take_pending_break());
+        assert_eq!(None, uart.take_pending_break());
+    }
+
+    #[test]
+    fn pending_break_of_zero_bit_times_is_a_no_op() {
+        let uart = UartT::default();
+
+        uart.set_pending_break(Some(0));
+
+        assert_eq!(None, uart.take_pending_break());
+    }
+
+    #[test]
+    fn break_until_tracks_a_single_hold_window() {
+        let uart = UartT::default();
+
+        assert_eq!(None, uart.break_until());
+
+        uart.set_break_until(1_000);
+        assert_eq!(Some(1_000), uart.break_until());
+
+        uart.clear_break_until();
+        assert_eq!(None, uart.break_until());
+    }
+
+    #[test]
+    fn held_error_persists_until_cleared() {
+        let uart = UartT::default();
+
+        assert_eq!(None, uart.held_error());
+
+        uart.set_held_error(1 << 4, 1_000);
+        assert_eq!(Some((1 << 4, 1_000)), uart.held_error());
+
+        // Reading it doesn't consume it - only `clear_held_error()` (called
+        // by `flush()` once the hold window has elapsed) does.
+        assert_eq!(Some((1 << 4, 1_000)), uart.held_error());
+
+        uart.clear_held_error();
+        assert_eq!(None, uart.held_error());
+    }
+
+    #[test]
+    fn fe_release_extends_but_never_shortens_an_existing_hold() {
+        let uart = UartT::default();
+
+        assert_eq!(None, uart.fe_release_at());
+
+        // An injected framing error holds FE until cycle 1_000...
+        uart.extend_fe_release(1_000);
+        assert_eq!(Some(1_000), uart.fe_release_at());
+
+        // ...then a break starts and needs FE held well past that - its
+        // longer deadline must win, not get clobbered by the earlier one.
+        uart.extend_fe_release(5_000);
+        assert_eq!(Some(5_000), uart.fe_release_at());
+
+        // A second, shorter hold (e.g. another injected framing error)
+        // must not pull the deadline back in while the break is still held.
+        uart.extend_fe_release(2_000);
+        assert_eq!(Some(5_000), uart.fe_release_at());
+
+        uart.clear_fe_release_at();
+        assert_eq!(None, uart.fe_release_at());
+    }
+
+    #[test]
+    fn ready_to_send_waits_a_full_frame_after_the_last_byte_has_gone_out() {
+        let uart = UartT::default();
+
+        assert!(uart.ready_to_send(0, 100));
+
+        uart.set_last_tx_cycle(0);
+
+        assert!(!uart.ready_to_send(50, 100));
+        assert!(uart.ready_to_send(100, 100));
+        assert!(uart.ready_to_send(150, 100));
+    }
+
+    #[test]
+    fn rx_overrun_is_flagged_only_once_the_buffer_is_full() {
+        let uart = UartT::default();
+
+        for i in 0..UartT::MAX_BYTES {
+            uart.rx_push(i as u16);
         }
+
+        assert!(!uart.take_rx_had_error());
+
+        uart.rx_push(0xff);
+
+        assert!(uart.take_rx_had_error());
+        assert!(!uart.take_rx_had_error(), "flag should be cleared after being taken");
+    }
+
+    #[test]
+    fn decode_baud_rate_matches_the_hal_formula() {
+        assert_eq!(10_000, Uart::decode_baud_rate(160_000, 0, false));
+
+        // A non-zero UBRRn divides the rate further.
+        assert_eq!(1_000, Uart::decode_baud_rate(160_000, 9, false));
+
+        // U2Xn halves the divisor, doubling the rate for the same UBRRn.
+        assert_eq!(20_000, Uart::decode_baud_rate(160_000, 0, true));
     }
 }
\ No newline at end of file
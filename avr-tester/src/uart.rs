@@ -1,5 +1,85 @@
 use crate::*;
 
+/// Configures a UART's frame format.
+///
+/// See: [`Uart::configure()`].
+///
+/// # Examples
+///
+/// ```no_run
+/// # use avr_tester::*;
+/// # fn avr() -> AvrTester { panic!() }
+/// #
+/// let mut avr = avr();
+///
+/// avr.uart0().configure(UartConfig {
+///     parity: Parity::Even,
+///     stop_bits: StopBits::Two,
+///     data_bits: DataBits::Eight,
+/// });
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct UartConfig {
+    pub parity: Parity,
+    pub stop_bits: StopBits,
+    pub data_bits: DataBits,
+}
+
+/// See: [`UartConfig`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Parity {
+    #[default]
+    None,
+    Even,
+    Odd,
+}
+
+/// See: [`UartConfig`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StopBits {
+    #[default]
+    One,
+    Two,
+}
+
+/// See: [`UartConfig`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DataBits {
+    #[default]
+    Eight,
+    Nine,
+}
+
+impl UartConfig {
+    /// Decodes a frame format from the AVR's `UCSRnB` and `UCSRnC` registers.
+    pub(crate) fn from_registers(ucsrnb: u8, ucsrnc: u8) -> Self {
+        let parity = match (ucsrnc >> 4) & 0b11 {
+            0b10 => Parity::Even,
+            0b11 => Parity::Odd,
+            _ => Parity::None,
+        };
+
+        let stop_bits = if ucsrnc & (1 << 3) != 0 {
+            StopBits::Two
+        } else {
+            StopBits::One
+        };
+
+        let ucsz = (((ucsrnb >> 2) & 1) << 2) | ((ucsrnc >> 1) & 0b11);
+        let data_bits = if ucsz == 0b111 {
+            DataBits::Nine
+        } else {
+            DataBits::Eight
+        };
+
+        Self {
+            parity,
+            stop_bits,
+            data_bits,
+        }
+    }
+}
+
 /// Provides access to the UART interface.
 ///
 /// See: [`Uart::read()`] and [`Uart::write()`].
@@ -13,6 +93,47 @@ impl<'a> Uart<'a> {
         Self { sim, id }
     }
 
+    /// Configures this UART's frame format (parity, stop bits, data bits).
+    ///
+    /// This affects the test-to-AVR path: bytes sent through [`Self::write()`]
+    /// are paced one frame (start bit, data bits, parity bit if any, stop
+    /// bits) apart, and with `DataBits::Nine` the 9th bit of each word is
+    /// delivered through `RXB8n`. No parity bit is computed or checked, and
+    /// this configuration is not compared against the firmware's `UCSRnC`.
+    ///
+    /// To see what the firmware itself has programmed, see
+    /// [`Self::data_bits()`], [`Self::parity()`] and [`Self::stop_bits()`].
+    pub fn configure(&mut self, config: UartConfig) {
+        self.sim.configure_uart(self.id, config);
+    }
+
+    /// Returns the number of data bits the firmware has programmed for this
+    /// UART, decoded from `UCSRnB` / `UCSRnC`.
+    pub fn data_bits(&mut self) -> DataBits {
+        self.sim.uart_frame_format(self.id).data_bits
+    }
+
+    /// Returns the parity mode the firmware has programmed for this UART,
+    /// decoded from `UCSRnC`.
+    pub fn parity(&mut self) -> Parity {
+        self.sim.uart_frame_format(self.id).parity
+    }
+
+    /// Returns the number of stop bits the firmware has programmed for this
+    /// UART, decoded from `UCSRnC`.
+    pub fn stop_bits(&mut self) -> StopBits {
+        self.sim.uart_frame_format(self.id).stop_bits
+    }
+
+    /// Returns the baud rate the firmware has programmed for this UART,
+    /// decoded from `UBRRn` and `U2Xn`.
+    ///
+    /// This is also the rate at which bytes queued by [`Self::write()`] are
+    /// paced onto the simulated RX line.
+    pub fn baud_rate(&mut self) -> u32 {
+        self.sim.uart_baud_rate(self.id)
+    }
+
     /// Retrieves a value from AVR.
     ///
     /// See: [`Readable`].
@@ -95,6 +216,58 @@ impl<'a> Uart<'a> {
     {
         value.write(self);
     }
+
+    /// Causes the next byte delivered to the AVR to arrive with a framing
+    /// error (sets `FEn` in `UCSRnA`).
+    pub fn inject_framing_error(&mut self) {
+        self.sim.inject_uart_error(self.id, LineError::Framing);
+    }
+
+    /// Causes the next byte delivered to the AVR to arrive with a parity
+    /// error (sets `UPEn` in `UCSRnA`).
+    pub fn inject_parity_error(&mut self) {
+        self.sim.inject_uart_error(self.id, LineError::Parity);
+    }
+
+    /// Causes the next byte delivered to the AVR to arrive as an overrun
+    /// (sets `DORn` in `UCSRnA`).
+    pub fn inject_overrun(&mut self) {
+        self.sim.inject_uart_error(self.id, LineError::Overrun);
+    }
+
+    /// Holds the simulated RX line low for `bit_times` bit-times, simulating
+    /// a break condition: firmware receives a single all-zero frame with
+    /// `FEn` set, `FEn` stays set until the break ends, and no queued bytes
+    /// are delivered in the meantime.
+    pub fn inject_break(&mut self, bit_times: u32) {
+        self.sim.inject_uart_error(self.id, LineError::Break { bit_times });
+    }
+}
+
+/// A line-level error condition to inject onto a UART's RX line.
+///
+/// See: [`Uart::inject_framing_error()`], [`Uart::inject_parity_error()`],
+/// [`Uart::inject_overrun()`] and [`Uart::inject_break()`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum LineError {
+    Framing,
+    Parity,
+    Overrun,
+    Break { bit_times: u32 },
+}
+
+impl LineError {
+    /// Returns the `UCSRnA` bit-mask to raise for this error, or `None` for
+    /// [`LineError::Break`] (which is handled separately, by holding the RX
+    /// line low rather than tagging a single byte).
+    pub(crate) fn ucsrna_mask(self) -> Option<u32> {
+        match self {
+            Self::Framing => Some(1 << 4),
+            Self::Parity => Some(1 << 2),
+            Self::Overrun => Some(1 << 3),
+            Self::Break { .. } => None,
+        }
+    }
 }
 
 impl Reader for Uart<'_> {
@@ -116,3 +289,171 @@ impl Writer for Uart<'_> {
         self.sim.write_uart(self.id, value);
     }
 }
+
+/// Reads a single 9-bit word, for UARTs configured with `DataBits::Nine`.
+///
+/// See: [`UartConfig`].
+impl Readable for u16 {
+    fn read(uart: &mut Uart) -> Self {
+        uart.sim.read_uart_word(uart.id).expect(
+            "UART's buffer is empty - got no more words to read; if you're \
+             receiving a large buffer, try running the simulator for a bit \
+             longer so that the simulated AVR has more time to respond",
+        )
+    }
+}
+
+/// Writes a single 9-bit word, for UARTs configured with `DataBits::Nine`.
+///
+/// See: [`UartConfig`].
+impl Writable for u16 {
+    fn write(self, uart: &mut Uart) {
+        uart.sim.write_uart_word(uart.id, self);
+    }
+}
+
+/// Asynchronous equivalent of [`Uart`].
+///
+/// See [`avr_rt()`] for more details.
+pub struct UartAsync {
+    id: char,
+}
+
+impl UartAsync {
+    pub(super) fn new(id: char) -> Self {
+        Self { id }
+    }
+
+    /// Reads bytes into `buf` until one of:
+    ///
+    /// - `buf` gets full,
+    /// - the RX line goes idle (no new byte arrives) for `idle_timeout`
+    ///   bit-times, as computed from [`Uart::baud_rate()`],
+    /// - this UART's receive queue overflows, because the AVR sent bytes
+    ///   faster than this call (or anything else) drained them and the
+    ///   newest incoming byte got dropped (already-buffered bytes are left
+    ///   alone).
+    ///
+    /// Note that [`Uart::inject_framing_error()`] et al. act on the opposite
+    /// direction (bytes going *into* the AVR), so they don't affect this
+    /// function - they're for exercising the firmware's own receive path,
+    /// not the test harness's. [`ReadEndReason::Overrun`] is therefore *not*
+    /// the "injected line error" this function was originally asked to
+    /// surface; it's the test harness's own receive-queue backpressure, a
+    /// condition none of the `inject_*` calls can produce. Tying this read
+    /// to an actually-injected line error would need the firmware itself to
+    /// report one back over the wire - there's no such signal to read here.
+    ///
+    /// Returns a [`ReadResult`] describing how many bytes were read and why
+    /// the read ended.
+    pub async fn read_until_idle(&mut self, buf: &mut [u8], idle_timeout: u32) -> ReadResult {
+        let idle_cycles = ComponentRuntime::with(|rt| {
+            let baud_rate = rt.sim().uart_baud_rate(self.id).max(1);
+
+            (rt.clock_frequency() / baud_rate) as u64 * idle_timeout as u64
+        });
+
+        let mut len = 0;
+        let mut idle_for = 0u64;
+
+        loop {
+            if len >= buf.len() {
+                return ReadResult::new(len, ReadEndReason::Filled);
+            }
+
+            if let Some(byte) = ComponentRuntime::with(|rt| rt.sim().read_uart(self.id)) {
+                buf[len] = byte;
+                len += 1;
+                idle_for = 0;
+
+                if ComponentRuntime::with(|rt| rt.sim().uart_take_error(self.id)) {
+                    return ReadResult::new(len, ReadEndReason::Overrun);
+                }
+
+                continue;
+            }
+
+            if ComponentRuntime::with(|rt| rt.sim().uart_take_error(self.id)) {
+                return ReadResult::new(len, ReadEndReason::Overrun);
+            }
+
+            let dt = avr_rt().run().await;
+
+            idle_for += dt.as_cycles();
+
+            if idle_for >= idle_cycles {
+                return ReadResult::new(len, ReadEndReason::Idle);
+            }
+        }
+    }
+}
+
+/// Outcome of [`UartAsync::read_until_idle()`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReadResult {
+    /// Number of bytes written into the caller's buffer.
+    pub len: usize,
+    /// Why the read stopped.
+    pub reason: ReadEndReason,
+}
+
+impl ReadResult {
+    fn new(len: usize, reason: ReadEndReason) -> Self {
+        Self { len, reason }
+    }
+}
+
+/// See: [`ReadResult`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReadEndReason {
+    /// The caller's buffer got full.
+    Filled,
+    /// No new byte arrived for the requested number of bit-times.
+    Idle,
+    /// This UART's receive queue overflowed - the AVR sent bytes faster than
+    /// they were drained, and the newest incoming byte was dropped
+    /// (already-buffered bytes are left untouched). This is the test
+    /// harness's own backpressure, not an injected line error - see
+    /// [`UartAsync::read_until_idle()`].
+    Overrun,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_registers_decodes_parity() {
+        assert_eq!(Parity::None, UartConfig::from_registers(0b000, 0b0000_0110).parity);
+        assert_eq!(Parity::Even, UartConfig::from_registers(0b000, 0b0010_0110).parity);
+        assert_eq!(Parity::Odd, UartConfig::from_registers(0b000, 0b0011_0110).parity);
+    }
+
+    #[test]
+    fn from_registers_decodes_stop_bits() {
+        assert_eq!(StopBits::One, UartConfig::from_registers(0b000, 0b0000_0110).stop_bits);
+        assert_eq!(StopBits::Two, UartConfig::from_registers(0b000, 0b0000_1110).stop_bits);
+    }
+
+    #[test]
+    fn from_registers_decodes_data_bits() {
+        // UCSZ = 0b011 (8 data bits): UCSZ1:0 in UCSRnC, UCSZ2 in UCSRnB.
+        assert_eq!(
+            DataBits::Eight,
+            UartConfig::from_registers(0b000, 0b0000_0110).data_bits,
+        );
+
+        // UCSZ = 0b111 (9 data bits): UCSZ1:0 = 0b11 in UCSRnC, UCSZ2 = 1 in UCSRnB.
+        assert_eq!(
+            DataBits::Nine,
+            UartConfig::from_registers(0b0000_0100, 0b0000_0110).data_bits,
+        );
+
+        // UCSZ = 0b001 (6 data bits) - anything other than 0b111 collapses to Eight here,
+        // since this crate only distinguishes between 8 and 9 data bits.
+        assert_eq!(
+            DataBits::Eight,
+            UartConfig::from_registers(0b000, 0b0000_0010).data_bits,
+        );
+    }
+}